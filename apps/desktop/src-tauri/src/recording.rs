@@ -3,17 +3,164 @@ use std::collections::HashSet;
 use std::io::{self, BufReader, BufRead, ErrorKind};
 use std::fs::File;
 use std::sync::{Arc, atomic::{AtomicBool, Ordering}};
-use tokio::sync:: {Mutex};
+use tokio::sync::{Mutex, watch};
 use tokio::task::JoinHandle;
 use tokio::time::{Duration};
 use serde::{Serialize, Deserialize};
-use tauri::State;
+use tauri::{AppHandle, Manager, State};
 use futures::future::join_all;
 
-use crate::upload::upload_file;
-
 use crate::media::MediaRecorder;
 
+mod storage;
+mod playlist;
+
+use storage::{backend_for_options, StorageBackend};
+use playlist::Playlist;
+
+/// Fixed chunk length the media recorder writes segments at; used as the
+/// HLS `#EXT-X-TARGETDURATION` since segments aren't re-probed for their
+/// actual duration.
+const SEGMENT_TARGET_DURATION_SECS: u64 = 2;
+
+/// How often `start_dual_recording` re-emits `RecordStatus::Recording` with
+/// an updated `elapsed`, so the frontend progress indicator actually ticks
+/// instead of staying frozen at zero for the whole recording.
+const RECORDING_ELAPSED_TICK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Where a recording currently sits in its lifecycle. Mirrors what the
+/// frontend shows as a progress indicator. The stop and upload logic itself
+/// still runs off the `shutdown_flag`/`*_uploading_finished` booleans.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RecordStatus {
+  Idle,
+  Waiting,
+  Recording { elapsed: Duration },
+  Stopping,
+  Finished,
+  Error(String),
+}
+
+/// Per-track upload progress, emitted to the frontend as segments land.
+#[derive(Debug, Clone, Serialize)]
+pub struct UploadProgress {
+  pub video_type: String,
+  pub segments_uploaded: usize,
+  pub bytes_uploaded: u64,
+}
+
+/// A segment whose upload has failed at least once, or that the final loop
+/// deferred because it hadn't finished being written yet. Tracked so
+/// `start_upload_loop` can retry it with backoff instead of silently
+/// dropping the chunk.
+struct PendingRetry {
+  attempts: u32,
+  next_attempt_at: tokio::time::Instant,
+  /// Set when this entry was deferred for being unstable rather than for a
+  /// failed upload. `spawn_due_retries` re-polls stability before spawning
+  /// an upload for it, instead of trusting the one-time check that deferred
+  /// it in the first place.
+  needs_stability_check: bool,
+  /// How many times `spawn_due_retries` has found this entry still
+  /// unstable. Capped at `MAX_STABILITY_CHECKS` so a segment that never
+  /// settles (or whose file vanished) still gets spawned — and so falls
+  /// through to the normal `attempts`/`MAX_UPLOAD_ATTEMPTS` accounting —
+  /// instead of looping `drain_pending_retries` forever.
+  stability_checks: u32,
+}
+
+const MAX_UPLOAD_ATTEMPTS: u32 = 5;
+const BASE_BACKOFF: Duration = Duration::from_millis(200);
+const MAX_BACKOFF: Duration = Duration::from_millis(800);
+
+fn backoff_for_attempt(attempts: u32) -> Duration {
+  let millis = BASE_BACKOFF.as_millis() as u64 * 2u64.saturating_pow(attempts.saturating_sub(1));
+  Duration::from_millis(millis).min(MAX_BACKOFF)
+}
+
+#[cfg(test)]
+mod backoff_tests {
+  use super::*;
+
+  #[test]
+  fn first_attempt_uses_base_backoff() {
+    assert_eq!(backoff_for_attempt(1), BASE_BACKOFF);
+  }
+
+  #[test]
+  fn backoff_doubles_per_attempt() {
+    assert_eq!(backoff_for_attempt(2), BASE_BACKOFF * 2);
+    assert_eq!(backoff_for_attempt(3), BASE_BACKOFF * 4);
+  }
+
+  #[test]
+  fn backoff_is_capped_at_max() {
+    assert_eq!(backoff_for_attempt(10), MAX_BACKOFF);
+  }
+}
+
+/// Segments below this size are treated as corrupt/empty rather than
+/// uploaded, since `MediaRecorder` can leave a zero-byte or truncated file
+/// behind for the chunk it was writing when recording stopped.
+const MIN_SEGMENT_SIZE_BYTES: u64 = 1024;
+
+/// Returns the file's size if it exists and meets `min_bytes`, `None`
+/// otherwise.
+fn segment_size_if_valid(path: &Path, min_bytes: u64) -> Option<u64> {
+  std::fs::metadata(path).ok().map(|m| m.len()).filter(|size| *size >= min_bytes)
+}
+
+/// Polls a file's size twice with a short gap to confirm it's no longer
+/// being written to, so the final upload pass doesn't ship a segment
+/// `MediaRecorder` hasn't finished flushing yet. Deliberately independent of
+/// `MIN_SEGMENT_SIZE_BYTES`: "is this file still growing" and "is this file
+/// big enough to keep" are separate questions, and conflating them means a
+/// segment that's genuinely done but smaller than the size floor (a short
+/// trailing fragment right before stop) would never be reported stable.
+async fn segment_size_if_stable(path: &Path) -> Option<u64> {
+  let first = std::fs::metadata(path).ok().map(|m| m.len())?;
+  tokio::time::sleep(Duration::from_millis(100)).await;
+  let second = std::fs::metadata(path).ok().map(|m| m.len())?;
+  (first == second).then_some(second)
+}
+
+/// How long to wait before re-checking a segment `spawn_due_retries` found
+/// still unstable, mirroring the gap `segment_size_if_stable` polls at.
+const STABILITY_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How many times `spawn_due_retries` will find a deferred segment still
+/// unstable before giving up on waiting and uploading it (or dropping it,
+/// via the normal undersized check) as-is.
+const MAX_STABILITY_CHECKS: u32 = 10;
+
+#[cfg(test)]
+mod segment_size_tests {
+  use super::*;
+
+  #[test]
+  fn missing_file_is_none() {
+    assert_eq!(segment_size_if_valid(Path::new("/nonexistent/segment.ts"), 1), None);
+  }
+
+  #[test]
+  fn file_below_min_bytes_is_none() {
+    let mut path = std::env::temp_dir();
+    path.push(format!("recording-test-small-{}.ts", std::process::id()));
+    std::fs::write(&path, b"x").unwrap();
+    assert_eq!(segment_size_if_valid(&path, 1024), None);
+    let _ = std::fs::remove_file(&path);
+  }
+
+  #[test]
+  fn file_at_or_above_min_bytes_is_some() {
+    let mut path = std::env::temp_dir();
+    path.push(format!("recording-test-large-{}.ts", std::process::id()));
+    std::fs::write(&path, vec![0u8; 2048]).unwrap();
+    assert_eq!(segment_size_if_valid(&path, 1024), Some(2048));
+    let _ = std::fs::remove_file(&path);
+  }
+}
+
 pub struct RecordingState {
   pub media_process: Option<MediaRecorder>,
   pub upload_handles: Mutex<Vec<JoinHandle<Result<(), String>>>>,
@@ -21,7 +168,15 @@ pub struct RecordingState {
   pub shutdown_flag: Arc<AtomicBool>,
   pub video_uploading_finished: Arc<AtomicBool>,
   pub audio_uploading_finished: Arc<AtomicBool>,
-  pub data_dir: Option<PathBuf>
+  pub data_dir: Option<PathBuf>,
+  pub storage_backend: Option<Arc<dyn StorageBackend + Send + Sync>>,
+  pub status: Arc<watch::Sender<RecordStatus>>,
+  /// Set while `start_dual_recording` is between `Waiting` and actually
+  /// committing its `shutdown_flag`/`media_process` into this state (i.e.
+  /// during `start_delay`). `shutdown_flag` isn't wired in yet at that
+  /// point, so `perform_stop` has nothing else to signal a pending start
+  /// with; flipping this tells it to abort instead of racing past the stop.
+  pub pending_start_cancel: Option<Arc<AtomicBool>>,
 }
 
 unsafe impl Send for RecordingState {}
@@ -38,23 +193,73 @@ pub struct RecordingOptions {
   pub audio_name: String,
   pub aws_region: String,
   pub aws_bucket: String,
+  /// When set, segments are copied into this local directory instead of
+  /// being uploaded anywhere. Takes precedence over `aws_bucket`.
+  #[serde(default)]
+  pub local_storage_dir: Option<PathBuf>,
+  /// When set, segments are PUT to `{upload_endpoint}/{key}` instead of S3.
+  /// Takes precedence over both `local_storage_dir` and `aws_bucket`.
+  #[serde(default)]
+  pub upload_endpoint: Option<String>,
+  /// How long to record before auto-stopping, measured from when the first
+  /// chunk is written (i.e. after `start_delay`, not from the command call).
+  #[serde(default)]
+  pub max_duration: Option<Duration>,
+  /// How long to wait in the `Waiting` state before `prepare_media_recording`
+  /// starts writing chunks.
+  #[serde(default)]
+  pub start_delay: Option<Duration>,
+}
+
+/// Updates the status watched by `RecordingState` and pushes it to the
+/// frontend as a `recording-status` event, so the frontend is told about
+/// each transition instead of having to poll for it.
+fn set_status(state: &RecordingState, app_handle: &AppHandle, status: RecordStatus) {
+  emit_status(&state.status, app_handle, status);
+}
+
+/// Same as `set_status`, but for callers that only hold the status sender
+/// (e.g. an upload loop) rather than the whole `RecordingState`.
+fn emit_status(status: &watch::Sender<RecordStatus>, app_handle: &AppHandle, new_status: RecordStatus) {
+  let _ = status.send(new_status.clone());
+  let _ = app_handle.emit_all("recording-status", new_status);
 }
 
 #[tauri::command]
 pub async fn start_dual_recording(
+  app_handle: AppHandle,
   state: State<'_, Arc<Mutex<RecordingState>>>,
   options: RecordingOptions,
 ) -> Result<(), String> {
   println!("Starting screen recording...");
   let mut state_guard = state.lock().await;
-  
+
+  set_status(&state_guard, &app_handle, RecordStatus::Waiting);
+
   let shutdown_flag = Arc::new(AtomicBool::new(false));
+  let status = state_guard.status.clone();
+  let start_cancel = Arc::new(AtomicBool::new(false));
+  state_guard.pending_start_cancel = Some(start_cancel.clone());
+
+  let data_dir = match state_guard.data_dir.as_ref() {
+    Some(dir) => dir.clone(),
+    None => {
+      let err = "Data directory is not set in the recording state".to_string();
+      set_status(&state_guard, &app_handle, RecordStatus::Error(err.clone()));
+      return Err(err);
+    }
+  };
 
-  let data_dir = state_guard.data_dir.as_ref()
-      .ok_or("Data directory is not set in the recording state".to_string())?.clone();
+  // Drop the lock before directory setup and the (potentially long)
+  // start_delay sleep, so `stop_all_recordings`/the max_duration timer can
+  // still acquire the lock while this recording is `Waiting` instead of
+  // blocking on it for the whole delay. `start_cancel` is how a stop
+  // requested during that window reaches us, since `shutdown_flag` isn't
+  // wired into the state until the recording actually commits below.
+  drop(state_guard);
 
   println!("data_dir: {:?}", data_dir);
-  
+
   let audio_chunks_dir = data_dir.join("chunks/audio");
   let video_chunks_dir = data_dir.join("chunks/video");
   let screenshot_dir = data_dir.join("screenshots");
@@ -62,15 +267,53 @@ pub async fn start_dual_recording(
   clean_and_create_dir(&audio_chunks_dir)?;
   clean_and_create_dir(&video_chunks_dir)?;
   clean_and_create_dir(&screenshot_dir)?;
-  
+
   let audio_name = if options.audio_name.is_empty() {
     None
   } else {
     Some(options.audio_name.clone())
   };
-  
+
+  if let Some(start_delay) = options.start_delay {
+    println!("Waiting {:?} before starting recording...", start_delay);
+    tokio::select! {
+      _ = tokio::time::sleep(start_delay) => {}
+      _ = wait_for_cancel(&start_cancel) => {
+        println!("Recording start cancelled during start_delay");
+        let guard = state.lock().await;
+        set_status(&guard, &app_handle, RecordStatus::Finished);
+        return Ok(());
+      }
+    }
+  }
+
+  if start_cancel.load(Ordering::SeqCst) {
+    println!("Recording start cancelled before media recording began");
+    let guard = state.lock().await;
+    set_status(&guard, &app_handle, RecordStatus::Finished);
+    return Ok(());
+  }
+
   let media_recording_preparation = prepare_media_recording(&options, &audio_chunks_dir, &video_chunks_dir, &screenshot_dir, audio_name);
-  let media_recording_result = media_recording_preparation.await.map_err(|e| e.to_string())?;
+  let media_recording_result = match media_recording_preparation.await {
+    Ok(recorder) => recorder,
+    Err(e) => {
+      let err = e.to_string();
+      emit_status(&status, &app_handle, RecordStatus::Error(err.clone()));
+      return Err(err);
+    }
+  };
+
+  let mut state_guard = state.lock().await;
+  state_guard.pending_start_cancel = None;
+
+  if start_cancel.load(Ordering::SeqCst) {
+    println!("Recording start cancelled while media recording was being prepared");
+    let mut media_recorder = media_recording_result;
+    let _ = media_recorder.stop_media_recording().await;
+    set_status(&state_guard, &app_handle, RecordStatus::Finished);
+    return Ok(());
+  }
 
   state_guard.media_process = Some(media_recording_result);
   state_guard.upload_handles = Mutex::new(vec![]);
@@ -79,8 +322,44 @@ pub async fn start_dual_recording(
   state_guard.video_uploading_finished = Arc::new(AtomicBool::new(false));
   state_guard.audio_uploading_finished = Arc::new(AtomicBool::new(false));
 
-  let screen_upload = start_upload_loop(video_chunks_dir.clone(), Some(screenshot_dir.clone()), options.clone(), "video".to_string(), shutdown_flag.clone(), state_guard.video_uploading_finished.clone());
-  let audio_upload = start_upload_loop(audio_chunks_dir, None, options.clone(), "audio".to_string(), shutdown_flag.clone(), state_guard.audio_uploading_finished.clone());
+  let storage_backend: Arc<dyn StorageBackend + Send + Sync> = Arc::from(backend_for_options(&options));
+  state_guard.storage_backend = Some(storage_backend.clone());
+
+  set_status(&state_guard, &app_handle, RecordStatus::Recording { elapsed: Duration::ZERO });
+
+  let recording_started_at = tokio::time::Instant::now();
+  let elapsed_ticker_status = state_guard.status.clone();
+  let elapsed_ticker_app_handle = app_handle.clone();
+  let elapsed_ticker_shutdown_flag = shutdown_flag.clone();
+  tokio::spawn(async move {
+    let mut ticker = tokio::time::interval(RECORDING_ELAPSED_TICK_INTERVAL);
+    ticker.tick().await; // first tick fires immediately; skip it
+    loop {
+      ticker.tick().await;
+      if elapsed_ticker_shutdown_flag.load(Ordering::SeqCst) {
+        break;
+      }
+      emit_status(&elapsed_ticker_status, &elapsed_ticker_app_handle, RecordStatus::Recording {
+        elapsed: recording_started_at.elapsed(),
+      });
+    }
+  });
+
+  let screen_upload = start_upload_loop(video_chunks_dir.clone(), Some(screenshot_dir.clone()), options.clone(), "video".to_string(), shutdown_flag.clone(), state_guard.video_uploading_finished.clone(), storage_backend.clone(), app_handle.clone(), state_guard.status.clone());
+  let audio_upload = start_upload_loop(audio_chunks_dir, None, options.clone(), "audio".to_string(), shutdown_flag.clone(), state_guard.audio_uploading_finished.clone(), storage_backend, app_handle.clone(), state_guard.status.clone());
+
+  if let Some(max_duration) = options.max_duration {
+    let state_for_timer = state.inner().clone();
+    let app_handle_for_timer = app_handle.clone();
+    let shutdown_flag_for_timer = shutdown_flag.clone();
+    tokio::spawn(async move {
+      tokio::time::sleep(max_duration).await;
+      if !shutdown_flag_for_timer.load(Ordering::SeqCst) {
+        println!("Max recording duration {:?} reached, stopping automatically.", max_duration);
+        let _ = perform_stop(app_handle_for_timer, state_for_timer).await;
+      }
+    });
+  }
 
   drop(state_guard);
 
@@ -93,6 +372,9 @@ pub async fn start_dual_recording(
       },
       Err(e) => {
           eprintln!("An error occurred: {}", e);
+          let guard = state.lock().await;
+          set_status(&guard, &app_handle, RecordStatus::Error(e.clone()));
+          return Err(e);
       },
   }
 
@@ -100,11 +382,29 @@ pub async fn start_dual_recording(
 }
 
 #[tauri::command]
-pub async fn stop_all_recordings(state: State<'_, Arc<Mutex<RecordingState>>>) -> Result<(), String> {
+pub async fn stop_all_recordings(app_handle: AppHandle, state: State<'_, Arc<Mutex<RecordingState>>>) -> Result<(), String> {
+    perform_stop(app_handle, state.inner().clone()).await
+}
+
+/// Shared by the `stop_all_recordings` command and the `max_duration` timer
+/// spawned from `start_dual_recording`, so an auto-stop takes the exact same
+/// graceful path a manual stop does.
+async fn perform_stop(app_handle: AppHandle, state: Arc<Mutex<RecordingState>>) -> Result<(), String> {
     let mut guard = state.lock().await;
-    
+
     println!("Stopping media recording...");
-    
+
+    set_status(&guard, &app_handle, RecordStatus::Stopping);
+
+    // A recording that's still `Waiting` on `start_delay` hasn't wired its
+    // `shutdown_flag`/`media_process` into this state yet, so there's
+    // nothing below for those checks to catch — signal the pending start
+    // directly so it aborts instead of finishing after we've already
+    // reported the stop as done.
+    if let Some(cancel) = guard.pending_start_cancel.take() {
+        cancel.store(true, Ordering::SeqCst);
+    }
+
     guard.shutdown_flag.store(true, Ordering::SeqCst);
 
     if let Some(mut media_process) = guard.media_process.take() {
@@ -112,17 +412,29 @@ pub async fn stop_all_recordings(state: State<'_, Arc<Mutex<RecordingState>>>) -
         media_process.stop_media_recording().await.expect("Failed to stop media recording");
     }
 
-    while !guard.video_uploading_finished.load(Ordering::SeqCst) 
+    while !guard.video_uploading_finished.load(Ordering::SeqCst)
         || !guard.audio_uploading_finished.load(Ordering::SeqCst) {
         println!("Waiting for uploads to finish...");
         tokio::time::sleep(Duration::from_millis(50)).await;
     }
-    
+
     println!("All recordings and uploads stopped.");
 
+    set_status(&guard, &app_handle, RecordStatus::Finished);
+
     Ok(())
 }
 
+/// Polls `flag` until it's set, so `start_dual_recording` can race it
+/// against `start_delay` in a `tokio::select!` and abort a pending start
+/// the moment `perform_stop` requests cancellation instead of sleeping
+/// through it.
+async fn wait_for_cancel(flag: &AtomicBool) {
+    while !flag.load(Ordering::SeqCst) {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+}
+
 fn clean_and_create_dir(dir: &Path) -> Result<(), String> {
     if dir.exists() {
         // Instead of just reading the directory, this will also handle subdirectories.
@@ -145,6 +457,42 @@ fn clean_and_create_dir(dir: &Path) -> Result<(), String> {
     }
 }
 
+/// The key a segment is stored under in the storage backend. Namespaced by
+/// track so the video and audio loops — which share one `storage_backend`
+/// and produce filenames from the same numbering scheme — can't clobber
+/// each other on backends that store by key directly (`LocalBackend`,
+/// `HttpBackend`). `S3Backend` ignores the key and keys off `RecordingOptions`
+/// instead, but every backend gets the same namespaced key for consistency.
+fn storage_key(video_type: &str, segment_filename: &str) -> String {
+    format!("{}/{}", video_type, segment_filename)
+}
+
+/// Uploads one segment and reports its filename back alongside the result
+/// so the caller can update `watched_segments`/`pending_retry` correctly.
+/// `Ok(None)` means the segment was empty/undersized and was deleted rather
+/// than uploaded; it's still treated as "done", just not counted as a
+/// playlist entry or progress byte.
+async fn upload_segment(
+    backend: Arc<dyn StorageBackend + Send + Sync>,
+    segment_filename: String,
+    segment_path: PathBuf,
+    content_type: String,
+) -> (String, Result<Option<u64>, String>) {
+    match segment_size_if_valid(&segment_path, MIN_SEGMENT_SIZE_BYTES) {
+        Some(segment_size) => {
+            println!("Uploading video for {}: {:?}", content_type, segment_path);
+            let key = storage_key(&content_type, &segment_filename);
+            let result = backend.put(&key, &segment_path, &content_type).await;
+            (segment_filename, result.map(|_| Some(segment_size)))
+        }
+        None => {
+            println!("Dropping empty/undersized segment {:?}", segment_path);
+            let _ = std::fs::remove_file(&segment_path);
+            (segment_filename, Ok(None))
+        }
+    }
+}
+
 async fn start_upload_loop(
     chunks_dir: PathBuf,
     screenshot_dir: Option<PathBuf>,
@@ -152,61 +500,139 @@ async fn start_upload_loop(
     video_type: String,
     shutdown_flag: Arc<AtomicBool>,
     uploading_finished: Arc<AtomicBool>,
+    storage_backend: Arc<dyn StorageBackend + Send + Sync>,
+    app_handle: AppHandle,
+    status: Arc<watch::Sender<RecordStatus>>,
 ) -> Result<(), String> {
     let mut watched_segments: HashSet<String> = HashSet::new();
+    let mut pending_retry: std::collections::HashMap<String, PendingRetry> = std::collections::HashMap::new();
+    // Segments that exhausted MAX_UPLOAD_ATTEMPTS. Kept separate from
+    // `watched_segments` (which means "confirmed done") so failures stay
+    // visible, but still folded into `already_handled` so a permanently
+    // failed segment isn't endlessly re-discovered from `segment_list.txt`.
+    let mut failed_segments: HashSet<String> = HashSet::new();
     let mut is_final_loop = false;
     let mut screenshot_uploaded = false;
+    let mut segments_uploaded = 0usize;
+    let mut bytes_uploaded = 0u64;
+    let mut playlist = Playlist::new();
 
     loop {
         let mut upload_tasks = vec![];
         if shutdown_flag.load(Ordering::SeqCst) {
             if is_final_loop {
+                drain_pending_retries(
+                    &mut pending_retry,
+                    &storage_backend,
+                    &chunks_dir,
+                    &video_type,
+                    &app_handle,
+                    &status,
+                    &mut watched_segments,
+                    &mut failed_segments,
+                    &mut segments_uploaded,
+                    &mut bytes_uploaded,
+                    &mut playlist,
+                ).await;
+                playlist.end();
+                upload_playlist(&playlist, &chunks_dir, &video_type, &storage_backend).await;
                 break;
             }
             is_final_loop = true;
         }
 
+        let pending_keys: HashSet<String> = pending_retry.keys().cloned().collect();
+        let already_handled: HashSet<String> = watched_segments
+            .union(&pending_keys)
+            .cloned()
+            .chain(failed_segments.iter().cloned())
+            .collect();
+
         let current_segments = load_segment_list(&chunks_dir.join("segment_list.txt"))
             .map_err(|e| e.to_string())?
-            .difference(&watched_segments)
+            .difference(&already_handled)
             .cloned()
             .collect::<HashSet<String>>();
 
         for segment_filename in &current_segments {
             let segment_path = chunks_dir.join(segment_filename);
-            if segment_path.is_file() {
-                let options_clone = options.clone();
-                let video_type_clone = video_type.clone();
-                let segment_path_clone = segment_path.clone();
-                // Create a task for each file to be uploaded
-                upload_tasks.push(tokio::spawn(async move {
-                    let filepath_str = segment_path_clone.to_str().unwrap_or_default().to_owned();
-                    println!("Uploading video for {}: {}", video_type_clone, filepath_str);
-                    upload_file(Some(options_clone), filepath_str, video_type_clone).await.map(|_| ())
-                }));
+            if !segment_path.is_file() {
+                continue;
             }
-            watched_segments.insert(segment_filename.clone());
+
+            if is_final_loop {
+                // This is the last pass before draining retries and exiting:
+                // make sure the segment isn't still being written before
+                // shipping it. If it isn't stable yet, hand it to the
+                // pending-retry drain below instead of uploading it now.
+                if segment_size_if_stable(&segment_path).await.is_none() {
+                    println!("Segment {:?} not yet stable, deferring to retry drain", segment_path);
+                    pending_retry.insert(segment_filename.clone(), PendingRetry {
+                        attempts: 0,
+                        next_attempt_at: tokio::time::Instant::now() + STABILITY_POLL_INTERVAL,
+                        needs_stability_check: true,
+                        stability_checks: 0,
+                    });
+                    continue;
+                }
+            }
+
+            upload_tasks.push(tokio::spawn(upload_segment(
+                storage_backend.clone(),
+                segment_filename.clone(),
+                segment_path,
+                video_type.clone(),
+            )));
         }
 
+        upload_tasks.extend(spawn_due_retries(&mut pending_retry, &storage_backend, &chunks_dir, &video_type).await);
+
         if let Some(screenshot_dir) = &screenshot_dir {
             let screenshot_path = screenshot_dir.join("screen-capture.jpg");
             if !screenshot_uploaded && screenshot_path.is_file() {
-                let options_clone = options.clone();
                 let video_type_clone = video_type.clone();
                 let screenshot_path_clone = screenshot_path.clone();
-                upload_tasks.push(tokio::spawn(async move {
-                    let filepath_str = screenshot_path_clone.to_str().unwrap_or_default().to_owned();
-                    println!("Uploading screenshot for {}: {}", video_type_clone, filepath_str);
+                let backend = storage_backend.clone();
+                tokio::spawn(async move {
                     tokio::time::sleep(Duration::from_secs(1)).await;
-                    upload_file(Some(options_clone), filepath_str, "screenshot".to_string()).await.map(|_| ())
-                }));
+                    if segment_size_if_valid(&screenshot_path_clone, 1).is_none() {
+                        println!("Dropping empty screenshot {:?}", screenshot_path_clone);
+                        let _ = std::fs::remove_file(&screenshot_path_clone);
+                        return;
+                    }
+                    println!("Uploading screenshot for {}: {:?}", video_type_clone, screenshot_path_clone);
+                    if let Err(e) = backend.put("screen-capture.jpg", &screenshot_path_clone, "screenshot").await {
+                        eprintln!("Screenshot upload failed for {}: {}", video_type_clone, e);
+                    }
+                });
                 screenshot_uploaded = true;
             }
         }
 
         // Await all initiated upload tasks in parallel
         if !upload_tasks.is_empty() {
-            let _ = join_all(upload_tasks).await;
+            let results = join_all(upload_tasks).await;
+            let (newly_uploaded, newly_failed) = apply_upload_results(
+                results,
+                &mut watched_segments,
+                &mut pending_retry,
+                &mut segments_uploaded,
+                &mut bytes_uploaded,
+                &video_type,
+            );
+            for segment_filename in newly_failed {
+                emit_status(&status, &app_handle, RecordStatus::Error(format!(
+                    "Giving up on segment {} for {} after {} attempts",
+                    segment_filename, video_type, MAX_UPLOAD_ATTEMPTS
+                )));
+                failed_segments.insert(segment_filename);
+            }
+            update_playlist(&mut playlist, newly_uploaded, &chunks_dir, &video_type, &storage_backend).await;
+            let _ = app_handle.emit_all("upload-progress", UploadProgress {
+                video_type: video_type.clone(),
+                segments_uploaded,
+                bytes_uploaded,
+            });
         }
 
         tokio::time::sleep(Duration::from_millis(100)).await;
@@ -215,6 +641,243 @@ async fn start_upload_loop(
     Ok(())
 }
 
+/// Folds a batch of `upload_segment` results into `watched_segments` (on
+/// success) or `pending_retry` (on failure, with the backoff for its next
+/// attempt), so a segment is only ever considered "done" after a confirmed
+/// upload. Returns the segments newly added to the playlist and the
+/// segments that just exhausted `MAX_UPLOAD_ATTEMPTS`; the caller is
+/// responsible for recording the latter in `failed_segments` and emitting
+/// a status event, since only it knows the track's status sender.
+fn apply_upload_results(
+    results: Vec<Result<(String, Result<Option<u64>, String>), tokio::task::JoinError>>,
+    watched_segments: &mut HashSet<String>,
+    pending_retry: &mut std::collections::HashMap<String, PendingRetry>,
+    segments_uploaded: &mut usize,
+    bytes_uploaded: &mut u64,
+    video_type: &str,
+) -> (Vec<String>, Vec<String>) {
+    let mut newly_uploaded = vec![];
+    let mut newly_failed = vec![];
+    for result in results {
+        let (segment_filename, upload_result) = match result {
+            Ok(pair) => pair,
+            Err(e) => {
+                eprintln!("Upload task for {} panicked: {}", video_type, e);
+                continue;
+            }
+        };
+
+        match upload_result {
+            // Dropped as empty/undersized: done, but not uploaded.
+            Ok(None) => {
+                pending_retry.remove(&segment_filename);
+                watched_segments.insert(segment_filename);
+            }
+            Ok(Some(size)) => {
+                pending_retry.remove(&segment_filename);
+                // The playlist URI must match the key the segment was
+                // actually stored under (see `storage_key`), not the bare
+                // filename, or the video/audio playlists point at whichever
+                // track's segment last overwrote the shared key.
+                newly_uploaded.push(storage_key(video_type, &segment_filename));
+                watched_segments.insert(segment_filename);
+                *segments_uploaded += 1;
+                *bytes_uploaded += size;
+            }
+            Err(e) => {
+                let attempts = pending_retry
+                    .get(&segment_filename)
+                    .map(|r| r.attempts)
+                    .unwrap_or(0)
+                    + 1;
+                eprintln!(
+                    "Upload failed for {} ({}), attempt {}/{}: {}",
+                    segment_filename, video_type, attempts, MAX_UPLOAD_ATTEMPTS, e
+                );
+                if attempts >= MAX_UPLOAD_ATTEMPTS {
+                    eprintln!(
+                        "Giving up on segment {} for {} after {} attempts",
+                        segment_filename, video_type, attempts
+                    );
+                    pending_retry.remove(&segment_filename);
+                    newly_failed.push(segment_filename);
+                } else {
+                    pending_retry.insert(segment_filename, PendingRetry {
+                        attempts,
+                        next_attempt_at: tokio::time::Instant::now() + backoff_for_attempt(attempts),
+                        needs_stability_check: false,
+                        stability_checks: 0,
+                    });
+                }
+            }
+        }
+    }
+    (newly_uploaded, newly_failed)
+}
+
+/// Rewrites the track's HLS playlist with any newly uploaded segments and
+/// ships it to the same storage backend so viewers can tail the recording
+/// while it's still in progress.
+async fn update_playlist(
+    playlist: &mut Playlist,
+    newly_uploaded: Vec<String>,
+    chunks_dir: &Path,
+    video_type: &str,
+    storage_backend: &Arc<dyn StorageBackend + Send + Sync>,
+) {
+    if newly_uploaded.is_empty() {
+        return;
+    }
+    for segment_filename in newly_uploaded {
+        playlist.add_segment(segment_filename);
+    }
+    upload_playlist(playlist, chunks_dir, video_type, storage_backend).await;
+}
+
+/// Writes the playlist's current contents to disk and uploads it, used both
+/// after new segments land and once more at shutdown to append
+/// `#EXT-X-ENDLIST`.
+async fn upload_playlist(
+    playlist: &Playlist,
+    chunks_dir: &Path,
+    video_type: &str,
+    storage_backend: &Arc<dyn StorageBackend + Send + Sync>,
+) {
+    let playlist_filename = format!("{}-index.m3u8", video_type);
+    let playlist_path = chunks_dir.join(&playlist_filename);
+    let content = playlist.render(SEGMENT_TARGET_DURATION_SECS);
+
+    if let Err(e) = tokio::fs::write(&playlist_path, &content).await {
+        eprintln!("Failed to write playlist for {}: {}", video_type, e);
+        return;
+    }
+    if let Err(e) = storage_backend
+        .put(&playlist_filename, &playlist_path, "application/vnd.apple.mpegurl")
+        .await
+    {
+        eprintln!("Failed to upload playlist for {}: {}", video_type, e);
+    }
+}
+
+/// Spawns an upload task for every due entry in `pending_retry`. Entries
+/// deferred from the final loop for being unstable (`needs_stability_check`)
+/// are re-polled with `segment_size_if_stable` first rather than spawned
+/// outright — otherwise a segment that was still growing moments ago gets
+/// shipped truncated on the very next retry cycle, defeating the point of
+/// the stability guard that deferred it. A still-unstable entry is
+/// rescheduled instead of spawned, up to `MAX_STABILITY_CHECKS`; past that
+/// it's spawned anyway so a segment that never settles can't stall
+/// `drain_pending_retries` forever.
+async fn spawn_due_retries(
+    pending_retry: &mut std::collections::HashMap<String, PendingRetry>,
+    storage_backend: &Arc<dyn StorageBackend + Send + Sync>,
+    chunks_dir: &Path,
+    video_type: &str,
+) -> Vec<JoinHandle<(String, Result<Option<u64>, String>)>> {
+    let now = tokio::time::Instant::now();
+    let due: Vec<String> = pending_retry
+        .iter()
+        .filter(|(_, retry)| retry.next_attempt_at <= now)
+        .map(|(filename, _)| filename.clone())
+        .collect();
+
+    let mut tasks = vec![];
+    for segment_filename in due {
+        let needs_stability_check = pending_retry
+            .get(&segment_filename)
+            .map(|r| r.needs_stability_check)
+            .unwrap_or(false);
+        let segment_path = chunks_dir.join(&segment_filename);
+
+        if needs_stability_check {
+            if segment_size_if_stable(&segment_path).await.is_none() {
+                let stability_checks = pending_retry
+                    .get(&segment_filename)
+                    .map(|r| r.stability_checks + 1)
+                    .unwrap_or(MAX_STABILITY_CHECKS);
+                if stability_checks < MAX_STABILITY_CHECKS {
+                    if let Some(retry) = pending_retry.get_mut(&segment_filename) {
+                        retry.next_attempt_at = tokio::time::Instant::now() + STABILITY_POLL_INTERVAL;
+                        retry.stability_checks = stability_checks;
+                    }
+                    continue;
+                }
+                println!(
+                    "Segment {:?} still unstable after {} checks, uploading as-is",
+                    segment_path, stability_checks
+                );
+            }
+            if let Some(retry) = pending_retry.get_mut(&segment_filename) {
+                retry.needs_stability_check = false;
+            }
+        }
+
+        tasks.push(tokio::spawn(upload_segment(
+            storage_backend.clone(),
+            segment_filename,
+            segment_path,
+            video_type.to_string(),
+        )));
+    }
+    tasks
+}
+
+/// Keeps retrying everything left in `pending_retry` (respecting backoff)
+/// until it's empty or every segment has exhausted `MAX_UPLOAD_ATTEMPTS`,
+/// so the final loop never marks uploading finished with chunks still
+/// outstanding.
+async fn drain_pending_retries(
+    pending_retry: &mut std::collections::HashMap<String, PendingRetry>,
+    storage_backend: &Arc<dyn StorageBackend + Send + Sync>,
+    chunks_dir: &Path,
+    video_type: &str,
+    app_handle: &AppHandle,
+    status: &watch::Sender<RecordStatus>,
+    watched_segments: &mut HashSet<String>,
+    failed_segments: &mut HashSet<String>,
+    segments_uploaded: &mut usize,
+    bytes_uploaded: &mut u64,
+    playlist: &mut Playlist,
+) {
+    while !pending_retry.is_empty() {
+        let now = tokio::time::Instant::now();
+        let next_attempt_at = pending_retry.values().map(|r| r.next_attempt_at).min();
+        if let Some(next_attempt_at) = next_attempt_at {
+            if next_attempt_at > now {
+                tokio::time::sleep(next_attempt_at - now).await;
+            }
+        }
+
+        let tasks = spawn_due_retries(pending_retry, storage_backend, chunks_dir, video_type).await;
+        if tasks.is_empty() {
+            continue;
+        }
+
+        let results = join_all(tasks).await;
+        let (newly_uploaded, newly_failed) = apply_upload_results(
+            results,
+            watched_segments,
+            pending_retry,
+            segments_uploaded,
+            bytes_uploaded,
+            video_type,
+        );
+        for segment_filename in newly_failed {
+            emit_status(status, app_handle, RecordStatus::Error(format!(
+                "Giving up on segment {} for {} after {} attempts",
+                segment_filename, video_type, MAX_UPLOAD_ATTEMPTS
+            )));
+            failed_segments.insert(segment_filename);
+        }
+        update_playlist(playlist, newly_uploaded, chunks_dir, video_type, storage_backend).await;
+        let _ = app_handle.emit_all("upload-progress", UploadProgress {
+            video_type: video_type.to_string(),
+            segments_uploaded: *segments_uploaded,
+            bytes_uploaded: *bytes_uploaded,
+        });
+    }
+}
+
 fn load_segment_list(segment_list_path: &Path) -> io::Result<HashSet<String>> {
     let file = File::open(segment_list_path)?;
     let reader = BufReader::new(file);
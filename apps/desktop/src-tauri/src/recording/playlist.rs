@@ -0,0 +1,83 @@
+/// Builds an HLS (`m3u8`) playlist from the segments a track has
+/// successfully uploaded so far, so a viewer can start playback before the
+/// recording finishes. `start_upload_loop` rewrites and re-uploads this
+/// alongside every newly confirmed segment.
+pub struct Playlist {
+    segments: Vec<String>,
+    ended: bool,
+}
+
+impl Playlist {
+    pub fn new() -> Self {
+        Self {
+            segments: Vec::new(),
+            ended: false,
+        }
+    }
+
+    /// Records a segment as uploaded. Segment filenames are zero-padded and
+    /// monotonically increasing, so sorting keeps the playlist in time order
+    /// even though uploads can complete out of order.
+    pub fn add_segment(&mut self, filename: String) {
+        if !self.segments.contains(&filename) {
+            self.segments.push(filename);
+            self.segments.sort();
+        }
+    }
+
+    pub fn end(&mut self) {
+        self.ended = true;
+    }
+
+    pub fn render(&self, target_duration_secs: u64) -> String {
+        let mut out = String::new();
+        out.push_str("#EXTM3U\n");
+        out.push_str("#EXT-X-VERSION:3\n");
+        out.push_str(&format!("#EXT-X-TARGETDURATION:{}\n", target_duration_secs));
+        out.push_str("#EXT-X-MEDIA-SEQUENCE:0\n");
+        for segment in &self.segments {
+            out.push_str(&format!("#EXTINF:{:.3},\n{}\n", target_duration_secs as f64, segment));
+        }
+        if self.ended {
+            out.push_str("#EXT-X-ENDLIST\n");
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_segment_ignores_duplicates() {
+        let mut playlist = Playlist::new();
+        playlist.add_segment("002.ts".to_string());
+        playlist.add_segment("002.ts".to_string());
+        assert_eq!(playlist.segments, vec!["002.ts".to_string()]);
+    }
+
+    #[test]
+    fn add_segment_keeps_playlist_time_ordered() {
+        let mut playlist = Playlist::new();
+        playlist.add_segment("002.ts".to_string());
+        playlist.add_segment("000.ts".to_string());
+        playlist.add_segment("001.ts".to_string());
+        assert_eq!(
+            playlist.segments,
+            vec!["000.ts".to_string(), "001.ts".to_string(), "002.ts".to_string()]
+        );
+    }
+
+    #[test]
+    fn render_omits_endlist_until_ended() {
+        let mut playlist = Playlist::new();
+        playlist.add_segment("000.ts".to_string());
+        assert!(!playlist.render(2).contains("#EXT-X-ENDLIST"));
+
+        playlist.end();
+        let rendered = playlist.render(2);
+        assert!(rendered.contains("#EXT-X-ENDLIST"));
+        assert!(rendered.contains("#EXTINF:2.000,\n000.ts"));
+    }
+}
@@ -0,0 +1,100 @@
+use std::path::Path;
+
+use async_trait::async_trait;
+
+use crate::upload::upload_file;
+
+use super::RecordingOptions;
+
+/// A destination recordings and screenshots can be shipped to once a chunk
+/// finishes writing. `start_upload_loop` talks to this trait instead of a
+/// hardcoded uploader so new destinations only need a new impl.
+#[async_trait]
+pub trait StorageBackend {
+    async fn put(&self, key: &str, path: &Path, content_type: &str) -> Result<(), String>;
+}
+
+/// Uploads to the S3 bucket configured on `RecordingOptions`. Wraps the
+/// existing `upload::upload_file` helper so the S3 request-signing logic
+/// isn't duplicated.
+pub struct S3Backend {
+    pub options: RecordingOptions,
+}
+
+#[async_trait]
+impl StorageBackend for S3Backend {
+    async fn put(&self, key: &str, path: &Path, content_type: &str) -> Result<(), String> {
+        let filepath_str = path.to_str().unwrap_or_default().to_owned();
+        upload_file(Some(self.options.clone()), filepath_str, content_type.to_string())
+            .await
+            .map(|_| ())
+            .map_err(|e| format!("S3 upload of {} failed: {}", key, e))
+    }
+}
+
+/// Copies into a directory on the local filesystem, for recording without
+/// any cloud credentials.
+pub struct LocalBackend {
+    pub dir: std::path::PathBuf,
+}
+
+#[async_trait]
+impl StorageBackend for LocalBackend {
+    async fn put(&self, key: &str, path: &Path, _content_type: &str) -> Result<(), String> {
+        let dest = self.dir.join(key);
+        if let Some(parent) = dest.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        tokio::fs::copy(path, &dest)
+            .await
+            .map(|_| ())
+            .map_err(|e| format!("local copy of {} to {:?} failed: {}", key, dest, e))
+    }
+}
+
+/// Reads the whole file into memory and sends it to a self-hosted endpoint
+/// as the body of one HTTP PUT.
+pub struct HttpBackend {
+    pub endpoint: String,
+}
+
+#[async_trait]
+impl StorageBackend for HttpBackend {
+    async fn put(&self, key: &str, path: &Path, content_type: &str) -> Result<(), String> {
+        let bytes = tokio::fs::read(path).await.map_err(|e| e.to_string())?;
+        let url = format!("{}/{}", self.endpoint.trim_end_matches('/'), key);
+
+        let client = reqwest::Client::new();
+        let response = client
+            .put(&url)
+            .header("Content-Type", content_type)
+            .body(bytes)
+            .send()
+            .await
+            .map_err(|e| format!("HTTP PUT to {} failed: {}", url, e))?;
+
+        if !response.status().is_success() {
+            return Err(format!("HTTP PUT to {} returned {}", url, response.status()));
+        }
+
+        Ok(())
+    }
+}
+
+/// Picks the backend implied by `RecordingOptions`. S3 remains the default
+/// so existing configs keep working unchanged.
+pub fn backend_for_options(options: &RecordingOptions) -> Box<dyn StorageBackend + Send + Sync> {
+    if let Some(endpoint) = &options.upload_endpoint {
+        Box::new(HttpBackend {
+            endpoint: endpoint.clone(),
+        })
+    } else if let Some(dir) = &options.local_storage_dir {
+        Box::new(LocalBackend { dir: dir.clone() })
+    } else {
+        Box::new(S3Backend {
+            options: options.clone(),
+        })
+    }
+}